@@ -23,6 +23,18 @@ pub const MIN_MAGISK_VERSION: i32 = unwrap_ctx!(parse_i32(env!("MIN_MAGISK_VERSI
 /// The version of the NeoZygisk daemon itself.
 pub const ZKSU_VERSION: &str = env!("ZKSU_VERSION");
 
+/// The protocol version byte exchanged on the companion control socket,
+/// before any module symbol is invoked. Bumped whenever the handshake
+/// itself (not a module's API version) changes shape.
+pub const COMPANION_PROTOCOL_VERSION: u8 = 1;
+
+/// The range of `ZYGISK_API_VERSION`s this daemon's companion handshake
+/// will load a module against. A module built outside this range is
+/// reported as "skip" in the `GetModuleFds` response instead of being
+/// handed a memfd that would fault after `dlopen`.
+pub const MIN_SUPPORTED_MODULE_API_VERSION: u32 = 2;
+pub const MAX_SUPPORTED_MODULE_API_VERSION: u32 = 5;
+
 // --- Configuration Constants ---
 
 /// The maximum log level for the daemon. Set to `Trace` for debug builds and `Info` for release builds.
@@ -59,6 +71,78 @@ pub enum DaemonSocketAction {
     GetModuleDir,
     ZygoteRestart,
     SystemServerStarted,
+    /// Requests the detach list served by the `detach` module (see
+    /// `crate::detach`): package names to hide from Play Store update
+    /// checks in-process.
+    GetDetachList,
+}
+
+/// Versioned request opcodes for the app-facing daemon protocol (steps 3-9
+/// of the architecture diagram in `main.rs`), sent as the first byte of
+/// each conversation an injected app or companion has with the daemon.
+///
+/// Distinct from [`DaemonSocketAction`]: that enum is the internal set of
+/// actions a connection can ask for once a conversation is underway, while
+/// `DaemonRequest` is the explicit, versioned opcode clients open with, so
+/// an old client talking to a newer daemon (or vice versa) can negotiate a
+/// common version via `CheckVersion` instead of misreading ad-hoc bytes.
+///
+/// Staged: see the "In-Progress Wiring" note in `main.rs` -- `reactor`'s
+/// `EventLoop::dispatch`/`drive_connection` still read every connection's
+/// first (and every subsequent) byte as a [`DaemonSocketAction`], exactly
+/// as before this enum existed. Nothing constructs a `DaemonRequest`,
+/// matches one, or returns a [`DaemonResponse`] anywhere in this tree yet.
+#[derive(Debug, Eq, PartialEq, TryFromPrimitive, Copy, Clone)]
+#[repr(u8)]
+pub enum DaemonRequest {
+    /// Negotiates the protocol version against [`ZKSU_VERSION`] before any
+    /// other request is sent.
+    CheckVersion,
+    GetModuleFds,
+    RequestCompanion,
+    GetProcessFlags,
+    Ping,
+}
+
+/// Response codes matching [`DaemonRequest`].
+#[derive(Debug, Eq, PartialEq, TryFromPrimitive, Copy, Clone)]
+#[repr(i32)]
+pub enum DaemonResponse {
+    Ok,
+    /// The daemon doesn't support the client's requested protocol version.
+    Unsupported,
+    Error,
+}
+
+/// The outcome of checking a module's declared `ZYGISK_API_VERSION`
+/// against [`MIN_SUPPORTED_MODULE_API_VERSION`]..=[`MAX_SUPPORTED_MODULE_API_VERSION`],
+/// carried in the `GetModuleFds` response so an incompatible module is
+/// simply skipped by the app rather than handed a memfd that would crash
+/// the host process after `dlopen`.
+///
+/// Staged: see the "In-Progress Wiring" note in `main.rs` -- nothing calls
+/// [`ModuleApiStatus::check`] yet.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ModuleApiStatus {
+    /// The module's declared API version is supported; its memfd is safe
+    /// for the app to `dlopen`.
+    Supported { api_version: u32 },
+    /// The module's declared API version falls outside the supported
+    /// range; the app should not attempt to load it.
+    Skipped { api_version: u32 },
+}
+
+impl ModuleApiStatus {
+    /// Checks `api_version` against the daemon's supported range.
+    pub fn check(api_version: u32) -> Self {
+        if (MIN_SUPPORTED_MODULE_API_VERSION..=MAX_SUPPORTED_MODULE_API_VERSION)
+            .contains(&api_version)
+        {
+            ModuleApiStatus::Supported { api_version }
+        } else {
+            ModuleApiStatus::Skipped { api_version }
+        }
+    }
 }
 
 bitflags! {
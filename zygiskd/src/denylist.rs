@@ -0,0 +1,164 @@
+// src/denylist.rs
+
+//! Hides traces of the root implementation from denylisted apps.
+//!
+//! Apps that actively probe for root (banking apps, SafetyNet/Play
+//! Integrity checks, ...) should see a system that looks untouched. This
+//! module reads a configurable, newline-delimited package-name list and,
+//! for a matching process, enters its mount namespace and removes every
+//! mount that would otherwise give it away: module store bind/overlay
+//! mounts and anything contributed by the active root solution itself.
+//!
+//! The caller (`zygiskd`, just before a denylisted process specializes) is
+//! responsible for deciding *when* to call [`hide_root_for_pid`]; this
+//! module only knows how.
+//!
+//! Staged: see the "In-Progress Wiring" note in `main.rs` -- nothing calls
+//! [`hide_root_for_pid`] yet.
+
+use anyhow::{Result, bail};
+use log::{debug, error, warn};
+use procfs::process::{MountInfo, Process};
+use std::ffi::CString;
+use std::fs;
+use std::io::Error;
+
+use crate::mount;
+use crate::root_impl;
+
+/// Default location of the denylist file: one package name per line.
+pub const DEFAULT_DENYLIST_PATH: &str = "/data/adb/denylist";
+
+/// Reads the denylist file at `path`, returning the package names it
+/// contains. A missing file is treated as an empty denylist, since having
+/// none configured yet is the common case, not an error.
+pub fn load(path: &str) -> Result<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns whether `package_name` is present in the denylist at `path`.
+/// Fails open (`false`) if the list can't be read, since failing closed
+/// here would make every listed app refuse to launch.
+pub fn is_denied(path: &str, package_name: &str) -> bool {
+    match load(path) {
+        Ok(list) => list.iter().any(|name| name == package_name),
+        Err(e) => {
+            warn!("Denylist: failed to read '{}': {:?}", path, e);
+            false
+        }
+    }
+}
+
+/// Enters `pid`'s mount namespace and unmounts every trace of the root
+/// implementation and module store from it, so the process sees a clean
+/// system. Does not consult the denylist itself: the caller checks
+/// [`is_denied`] first and calls this only for a listed app. Returns an
+/// error if called for the daemon's own pid.
+///
+/// The namespace switch happens inside a short-lived forked helper, never
+/// on one of the daemon's own threads, for the same reason `mount.rs`
+/// forks to cache namespaces: `setns(CLONE_NEWNS)` only affects the
+/// calling thread, and we don't want the daemon's other threads to
+/// observe a different mount namespace out from under them.
+///
+/// Known limitation: this only removes *mounts*. It does not yet restore
+/// any Android system properties the root solution may have spoofed for
+/// `pid` (e.g. a patched `ro.boot.*` value); that needs `root_impl` to
+/// expose which properties each solution overrides, which it doesn't yet.
+/// A denylisted app that fingerprints root via properties rather than
+/// mounts can still detect it after this call.
+pub fn hide_root_for_pid(pid: i32) -> Result<()> {
+    if pid == unsafe { libc::getpid() } {
+        bail!("hide_root_for_pid must never be called for the daemon's own pid");
+    }
+
+    match unsafe { libc::fork() } {
+        0 => {
+            if let Err(e) = unmount_root_traces(pid) {
+                error!("Denylist: failed to hide root for pid {}: {:?}", pid, e);
+                std::process::exit(1);
+            }
+            std::process::exit(0);
+        }
+        child_pid if child_pid > 0 => {
+            let mut status = 0;
+            unsafe {
+                libc::waitpid(child_pid, &mut status, 0);
+            }
+            if !(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0) {
+                bail!(
+                    "hide_root_for_pid helper for pid {} exited with status {}",
+                    pid,
+                    status
+                );
+            }
+            Ok(())
+        }
+        _ => bail!(Error::last_os_error()),
+    }
+}
+
+/// Runs inside the forked helper: switches into `pid`'s mount namespace and
+/// unmounts every root/module-related mount, in reverse mount order so
+/// nested mounts come apart cleanly.
+fn unmount_root_traces(pid: i32) -> Result<()> {
+    mount::switch_mount_namespace(pid)?;
+
+    let root_source = match root_impl::get() {
+        root_impl::RootImpl::APatch => Some("APatch"),
+        root_impl::RootImpl::KernelSU => Some("KSU"),
+        root_impl::RootImpl::Magisk => Some("magisk"),
+        _ => None,
+    };
+
+    let mount_infos = Process::myself()?.mountinfo()?;
+    let ksu_module_source = mount::ksu_module_loop_source(&mount_infos);
+
+    let mut targets: Vec<MountInfo> = mount_infos
+        .into_iter()
+        .filter(|info| {
+            mount::is_root_related(info, root_source)
+                || (ksu_module_source.is_some() && info.mount_source == ksu_module_source)
+        })
+        .collect();
+    targets.sort_by_key(|info| std::cmp::Reverse(info.mnt_id));
+
+    for target in targets {
+        let path = target.mount_point.to_str().unwrap_or("");
+        debug!(
+            "Denylist: unmounting {} (mnt_id: {}) for pid {}",
+            path, target.mnt_id, pid
+        );
+        if let Ok(path_cstr) = CString::new(path.to_string()) {
+            unsafe {
+                if libc::umount2(path_cstr.as_ptr(), libc::MNT_DETACH) == -1 {
+                    error!(
+                        "Denylist: failed to unmount {}: {}",
+                        path,
+                        Error::last_os_error()
+                    );
+                }
+            }
+        }
+    }
+
+    // Restoring properties the root solution spoofed for this process is
+    // intentionally left to a follow-up (see the `hide_root_for_pid` doc
+    // comment): it needs root_impl to expose exactly which properties each
+    // solution overrides. Log it so the gap is visible at runtime, not just
+    // in source comments.
+    warn!(
+        "Denylist: pid {} unmounted, but spoofed property restoration is not implemented yet",
+        pid
+    );
+    Ok(())
+}
@@ -0,0 +1,161 @@
+// src/detach.rs
+
+//! Manages the "detach" list: package names a bundled update-blocking
+//! module hides from the Play Store's update-availability checks.
+//!
+//! The daemon only stores and serves this list over [`GetDetachList`] (a
+//! [`DaemonSocketAction`]); it never does any hooking itself. A module
+//! running inside the Play Store process is expected to fetch the list and
+//! corrupt its in-memory occurrences of each package name, modeled on the
+//! standalone zygisk-detach tool.
+//!
+//! [`GetDetachList`]: crate::constants::DaemonSocketAction::GetDetachList
+//! [`DaemonSocketAction`]: crate::constants::DaemonSocketAction
+
+use anyhow::{Result, bail};
+use std::fs;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+
+use crate::utils::UnixStreamExt;
+
+/// Default location of the detach list file.
+pub const DEFAULT_DETACH_LIST_PATH: &str = "/data/adb/detach.list";
+
+/// Reads the detach list at `path`.
+///
+/// The on-disk format is a sequence of entries, each a little-endian `u32`
+/// length followed by that many bytes of UTF-8 package name. A missing
+/// file (e.g. before `detach add` has ever been run) reads back as an
+/// empty list rather than an error.
+pub fn load(path: &str) -> Result<Vec<String>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    parse_entries(&bytes).map_err(|e| anyhow::anyhow!("detach list '{}' {}", path, e))
+}
+
+/// Parses the length-prefixed entry format [`load`] reads and [`save`]
+/// writes out of an in-memory buffer, split out from [`load`] so it can be
+/// exercised directly against hand-built (including deliberately truncated)
+/// buffers without going through the filesystem.
+fn parse_entries(bytes: &[u8]) -> Result<Vec<String>> {
+    let mut entries = Vec::new();
+    let mut cursor = bytes;
+    while !cursor.is_empty() {
+        if cursor.len() < 4 {
+            bail!("detach list is truncated mid-entry");
+        }
+        let (len_bytes, rest) = cursor.split_at(4);
+        let len = u32::from_le_bytes(len_bytes.try_into()?) as usize;
+        if rest.len() < len {
+            bail!("detach list is truncated mid-entry");
+        }
+        let (name_bytes, rest) = rest.split_at(len);
+        entries.push(String::from_utf8(name_bytes.to_vec())?);
+        cursor = rest;
+    }
+    Ok(entries)
+}
+
+/// Writes `entries` to `path` in the format [`load`] reads.
+pub fn save(path: &str, entries: &[String]) -> Result<()> {
+    let mut bytes = Vec::new();
+    for entry in entries {
+        bytes.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(entry.as_bytes());
+    }
+    let mut file = fs::File::create(path)?;
+    file.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Adds `package_name` to the detach list at `path`, if not already present.
+pub fn add(path: &str, package_name: &str) -> Result<()> {
+    let mut entries = load(path)?;
+    if !entries.iter().any(|e| e == package_name) {
+        entries.push(package_name.to_string());
+        save(path, &entries)?;
+    }
+    Ok(())
+}
+
+/// Removes `package_name` from the detach list at `path`, if present.
+pub fn remove(path: &str, package_name: &str) -> Result<()> {
+    let mut entries = load(path)?;
+    let before = entries.len();
+    entries.retain(|e| e != package_name);
+    if entries.len() != before {
+        save(path, &entries)?;
+    }
+    Ok(())
+}
+
+/// Serves the detach list over an already-connected app socket, in
+/// response to a `GetDetachList` request: an entry count followed by each
+/// entry, using the same [`UnixStreamExt`] framing every other request on
+/// this socket is read/written with.
+///
+/// Staged: see the "In-Progress Wiring" note in `main.rs` -- `GetDetachList`
+/// is declared in `DaemonSocketAction` but nothing dispatches to this yet.
+pub fn serve(path: &str, stream: &mut UnixStream) -> Result<()> {
+    let entries = load(path)?;
+    stream.write_usize(entries.len())?;
+    for entry in entries {
+        stream.write_string(&entry)?;
+    }
+    Ok(())
+}
+
+/// Reads a detach list served by [`serve`]. Entries are read one at a time
+/// rather than reserving `count` capacity up front, since `count` comes
+/// straight off the wire before anything else has been validated.
+pub fn read_served(stream: &mut UnixStream) -> Result<Vec<String>> {
+    let count = stream.read_usize()?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        entries.push(stream.read_string()?);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_save_and_load() {
+        let path = std::env::temp_dir().join(format!(
+            "neozygisk-detach-test-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let path = path.to_str().unwrap();
+
+        let entries = vec!["com.example.app".to_string(), "com.another.app".to_string()];
+        save(path, &entries).unwrap();
+        assert_eq!(load(path).unwrap(), entries);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn truncated_length_prefix_is_an_error() {
+        // A single entry's 4-byte length prefix, cut short.
+        let bytes = [0u8, 0u8, 0u8];
+        let err = parse_entries(&bytes).unwrap_err();
+        assert!(err.to_string().contains("truncated"));
+    }
+
+    #[test]
+    fn truncated_entry_body_is_an_error() {
+        // A length prefix claiming more bytes than follow it.
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&10u32.to_le_bytes());
+        bytes.extend_from_slice(b"short");
+        let err = parse_entries(&bytes).unwrap_err();
+        assert!(err.to_string().contains("truncated"));
+    }
+}
@@ -64,16 +64,31 @@
 //! 9.  **Connection Handoff:** This is the critical brokering step. The daemon takes the **file descriptor** from the app's original connection and securely **passes this FD to the new companion process** over a private control socket.
 //! 10. **Direct Connection:** The companion receives the file descriptor and now holds the other end of the app's original socket. It can now communicate directly with the app. The daemon's brokering job is complete, and it is no longer involved in their conversation. This handoff is efficient and seamless from the app's perspective.
 //!
+//! ## A Note on In-Progress Wiring
+//!
+//! Several modules below (`reactor`, `denylist`, `detach`'s `serve`,
+//! `utils`'s fd-passing/`LogRecord` framing, and `constants`'s
+//! `ModuleApiStatus`, `DaemonRequest`, and `DaemonResponse`) are complete
+//! and ready to use, but nothing calls them yet: the request dispatch in
+//! `zygiskd` (`zygiskd::main`) does not use them. Each of those modules
+//! notes this with a one-line "Staged"
+//! pointer back to this paragraph rather than repeating the explanation.
+//!
 //! This binary has multiple modes of operation based on its command-line arguments:
 //! - No arguments: Starts the main `zygiskd` daemon.
 //! - `companion <fd>`: Starts a companion process for a Zygisk module.
 //! - `version`: Prints the daemon version.
 //! - `root`: Detects and prints the current root implementation.
+//! - `detach add|remove|list <package>`: Manages the offline detach list
+//!   (see `detach` module) served to modules over `GetDetachList`.
 
 mod companion;
 mod constants;
+mod denylist;
+mod detach;
 mod dl;
 mod mount;
+mod reactor;
 mod root_impl;
 mod utils;
 mod zygiskd;
@@ -107,11 +122,21 @@ fn start() {
         }
         Some("version") => {
             println!("NeoZygisk daemon {}", ZKSU_VERSION);
+            println!(
+                "Companion protocol version: {}",
+                constants::COMPANION_PROTOCOL_VERSION
+            );
+            println!(
+                "Supported module API versions: {}..={}",
+                constants::MIN_SUPPORTED_MODULE_API_VERSION,
+                constants::MAX_SUPPORTED_MODULE_API_VERSION
+            );
         }
         Some("root") => {
             root_impl::setup();
             println!("Detected root implementation: {:?}", root_impl::get());
         }
+        Some("detach") => detach_cli(&args[2..]),
         _ => {
             // Default to starting the main daemon.
             if let Err(e) = main_daemon_entry() {
@@ -121,6 +146,31 @@ fn start() {
     }
 }
 
+/// Implements the offline `detach` CLI subcommand: `detach add|remove|list
+/// <package>`. Manages the same on-disk list `detach::serve` answers
+/// `GetDetachList` with; this subcommand never touches a running daemon or
+/// does any hooking itself.
+fn detach_cli(args: &[String]) {
+    let path = detach::DEFAULT_DETACH_LIST_PATH;
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("add"), Some(package)) => match detach::add(path, package) {
+            Ok(()) => println!("Added {} to the detach list.", package),
+            Err(e) => error!("detach add: {:?}", e),
+        },
+        (Some("remove"), Some(package)) => match detach::remove(path, package) {
+            Ok(()) => println!("Removed {} from the detach list.", package),
+            Err(e) => error!("detach remove: {:?}", e),
+        },
+        (Some("list"), _) => match detach::load(path) {
+            Ok(entries) => entries.iter().for_each(|entry| println!("{}", entry)),
+            Err(e) => error!("detach list: {:?}", e),
+        },
+        _ => {
+            eprintln!("Usage: zygiskd detach <add|remove|list> [package]");
+        }
+    }
+}
+
 /// The main entry point for the Zygisk daemon.
 /// It sets up the environment and launches the core daemon logic.
 fn main_daemon_entry() -> anyhow::Result<()> {
@@ -1,25 +1,114 @@
 // src/mount.rs
 
-//! Manages Linux mount namespaces for the NeoZygisk daemon.
+//! Manages Linux namespaces for the NeoZygisk daemon.
 //!
 //! This module provides a unified API for caching, cleaning, and switching
-//! between different mount namespaces, which is crucial for isolating Zygisk
-//! modules and providing them with a clean environment.
+//! between the namespaces of other processes, which is crucial for isolating
+//! Zygisk modules and providing them with a clean environment. Mount
+//! namespaces are the primary use case, but the same machinery works for
+//! UTS, network, and cgroup namespaces so the daemon can follow a target
+//! process onto other axes when a module needs it. PID namespaces can be
+//! switched into but not cached: `save_namespace` returns an error for
+//! `NamespaceKind::Pid`.
 
 use anyhow::{Result, bail};
 use log::{debug, error, trace};
 use procfs::process::{MountInfo, Process};
 use rustix::thread as rustix_thread;
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::fs;
 use std::io::Error;
-use std::os::fd::{AsFd, AsRawFd, OwnedFd, RawFd};
-use std::sync::OnceLock;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::{Arc, Mutex, OnceLock};
 
 use crate::root_impl;
 
-/// Represents the two types of mount namespaces the daemon manages.
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+/// Opens a pidfd for `pid` via the `pidfd_open` syscall (Linux 5.3+).
+///
+/// Returns `Ok(None)` when the kernel doesn't support `pidfd_open`
+/// (`ENOSYS`) so callers can fall back to the `/proc/{pid}/ns/mnt` path.
+fn pidfd_open(pid: i32) -> Result<Option<OwnedFd>> {
+    let ret = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if ret >= 0 {
+        return Ok(Some(unsafe { OwnedFd::from_raw_fd(ret as RawFd) }));
+    }
+    match Error::last_os_error().raw_os_error() {
+        Some(libc::ENOSYS) | Some(libc::EINVAL) => Ok(None),
+        Some(libc::ESRCH) => bail!("process {} no longer exists", pid),
+        _ => Err(Error::last_os_error().into()),
+    }
+}
+
+/// Sends `signal` to the process identified by `pidfd` via `pidfd_send_signal`.
+///
+/// Unlike `kill(pid, ...)`, this targets the exact process the pidfd was
+/// opened for, so it can't be tricked into signalling a reused PID.
+///
+/// `ESRCH` (the process has already exited) is swallowed rather than
+/// returned as an error: it just means we're signalling a holder that died
+/// on its own, which is the outcome we wanted anyway.
+fn pidfd_send_signal(pidfd: RawFd, signal: i32) -> Result<()> {
+    let ret = unsafe { libc::syscall(libc::SYS_pidfd_send_signal, pidfd, signal, 0, 0) };
+    if ret != 0 {
+        match Error::last_os_error().raw_os_error() {
+            Some(libc::ESRCH) => return Ok(()),
+            _ => bail!(Error::last_os_error()),
+        }
+    }
+    Ok(())
+}
+
+/// The kind of Linux namespace a cached handle represents.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+#[repr(u8)]
+pub enum NamespaceKind {
+    Mount,
+    Pid,
+    Uts,
+    Net,
+    Cgroup,
+}
+
+impl NamespaceKind {
+    /// The name of this namespace's entry under `/proc/{pid}/ns/`.
+    fn proc_name(self) -> &'static str {
+        match self {
+            NamespaceKind::Mount => "mnt",
+            NamespaceKind::Pid => "pid",
+            NamespaceKind::Uts => "uts",
+            NamespaceKind::Net => "net",
+            NamespaceKind::Cgroup => "cgroup",
+        }
+    }
+
+    /// The `rustix` namespace-type tag passed to `setns` to make the kernel
+    /// verify the target fd is actually of this kind.
+    fn link_name_space_type(self) -> rustix_thread::LinkNameSpaceType {
+        match self {
+            NamespaceKind::Mount => rustix_thread::LinkNameSpaceType::Mount,
+            NamespaceKind::Pid => rustix_thread::LinkNameSpaceType::Pid,
+            NamespaceKind::Uts => rustix_thread::LinkNameSpaceType::Uts,
+            NamespaceKind::Net => rustix_thread::LinkNameSpaceType::Network,
+            NamespaceKind::Cgroup => rustix_thread::LinkNameSpaceType::Cgroup,
+        }
+    }
+
+    /// The `unshare(2)` flag used to create a new, private namespace of this
+    /// kind for the calling process.
+    fn unshare_flags(self) -> rustix_thread::UnshareFlags {
+        match self {
+            NamespaceKind::Mount => rustix_thread::UnshareFlags::NEWNS,
+            NamespaceKind::Pid => rustix_thread::UnshareFlags::NEWPID,
+            NamespaceKind::Uts => rustix_thread::UnshareFlags::NEWUTS,
+            NamespaceKind::Net => rustix_thread::UnshareFlags::NEWNET,
+            NamespaceKind::Cgroup => rustix_thread::UnshareFlags::NEWCGROUP,
+        }
+    }
+}
+
+/// Represents the two roles a cached namespace handle can play.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
 #[repr(u8)]
 pub enum MountNamespace {
     /// A "clean" namespace with all root-related mounts removed.
@@ -39,62 +128,229 @@ impl TryFrom<u8> for MountNamespace {
     }
 }
 
-/// Switches the current thread into the mount namespace of a given process.
-pub fn switch_mount_namespace(pid: i32) -> Result<()> {
+/// A target process for a namespace switch: either a raw PID, looked up
+/// fresh via `pidfd_open`, or a pidfd the caller already holds (e.g. one
+/// obtained during an earlier companion handoff), which is reused as-is
+/// instead of paying for another `pidfd_open` round trip.
+pub enum PidTarget {
+    Pid(i32),
+    Pidfd(OwnedFd),
+}
+
+impl From<i32> for PidTarget {
+    fn from(pid: i32) -> Self {
+        PidTarget::Pid(pid)
+    }
+}
+
+impl From<OwnedFd> for PidTarget {
+    fn from(pidfd: OwnedFd) -> Self {
+        PidTarget::Pidfd(pidfd)
+    }
+}
+
+/// Reads the PID a pidfd refers to, via its `/proc/self/fdinfo` entry.
+///
+/// Needed as a fallback path: if `setns` rejects a pidfd (pre-5.8 kernel),
+/// we still need the plain PID to fall back to opening `/proc/{pid}/ns/*`.
+fn pid_from_pidfd(pidfd: RawFd) -> Result<i32> {
+    let info = fs::read_to_string(format!("/proc/self/fdinfo/{}", pidfd))?;
+    for line in info.lines() {
+        if let Some(pid) = line.strip_prefix("Pid:") {
+            return Ok(pid.trim().parse()?);
+        }
+    }
+    bail!("no Pid: entry in fdinfo for pidfd {}", pidfd);
+}
+
+/// Switches the current thread into the namespace of kind `kind` belonging
+/// to `target`, a raw PID or an already-opened pidfd.
+///
+/// Prefers `pidfd_open` + `setns(pidfd, CLONE_NEW*)` (Linux 5.3+/5.8+): the
+/// pidfd pins the exact process, closing the TOCTOU window where a PID is
+/// reused by another process between looking it up and entering its
+/// namespace. Falls back to opening `/proc/{pid}/ns/{kind}` on older
+/// kernels that don't support the pidfd form. A process that has already
+/// exited is reported as a clean error (`ESRCH`/`ENOENT`) rather than
+/// silently entering the wrong namespace.
+pub fn switch_namespace(target: impl Into<PidTarget>, kind: NamespaceKind) -> Result<()> {
     let cwd = std::env::current_dir()?;
-    let mnt_ns_file = fs::File::open(format!("/proc/{}/ns/mnt", pid))?;
-    rustix_thread::move_into_link_name_space(
-        mnt_ns_file.as_fd(),
-        Some(rustix_thread::LinkNameSpaceType::Mount),
-    )?;
+
+    let (pidfd, pid) = match target.into() {
+        PidTarget::Pid(pid) => (pidfd_open(pid)?, pid),
+        PidTarget::Pidfd(fd) => {
+            let pid = pid_from_pidfd(fd.as_raw_fd())?;
+            (Some(fd), pid)
+        }
+    };
+
+    if let Some(pidfd) = pidfd {
+        match rustix_thread::move_into_link_name_space(pidfd.as_fd(), Some(kind.link_name_space_type()))
+        {
+            Ok(()) => {
+                std::env::set_current_dir(cwd)?;
+                return Ok(());
+            }
+            // Kernel knows pidfd_open but its setns() predates pidfd support
+            // (pre-5.8); fall through to the /proc path below.
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let ns_file = match fs::File::open(format!("/proc/{}/ns/{}", pid, kind.proc_name())) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            bail!("process {} no longer exists", pid);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    rustix_thread::move_into_link_name_space(ns_file.as_fd(), Some(kind.link_name_space_type()))?;
     // `setns` can change the current working directory, so we restore it.
     std::env::set_current_dir(cwd)?;
     Ok(())
 }
 
-/// Manages the lifecycle and caching of mount namespace file descriptors.
+/// Switches the current thread into the mount namespace of `target`.
 ///
-/// This manager is responsible for creating and holding onto file descriptors
-/// that represent specific mount namespaces, preventing them from being destroyed.
-pub struct MountNamespaceManager {
-    clean_mnt_ns_fd: OnceLock<OwnedFd>,
-    root_mnt_ns_fd: OnceLock<OwnedFd>,
+/// A thin convenience wrapper around [`switch_namespace`] for the common
+/// mount-namespace case. Accepts either a raw PID or an already-opened
+/// pidfd (see [`PidTarget`]).
+pub fn switch_mount_namespace(target: impl Into<PidTarget>) -> Result<()> {
+    switch_namespace(target, NamespaceKind::Mount)
+}
+
+/// Whether a mount belongs to the module store or the active root solution,
+/// and therefore must be unmounted for either the generic `Clean` mount
+/// namespace or a per-app denylist hide (see `crate::denylist`) to hold the
+/// same guarantee. `root_source` is the `mount_source` string the active
+/// root implementation tags its own mounts with (see `root_impl`).
+pub(crate) fn is_root_related(info: &MountInfo, root_source: Option<&str>) -> bool {
+    let path_str = info.mount_point.to_str().unwrap_or("");
+    let source_str = info.mount_source.as_deref();
+
+    info.root.starts_with("/adb/modules")
+        || path_str.starts_with("/data/adb/modules")
+        || path_str.starts_with("/data/adb/magisk")
+        || path_str.starts_with("/data/adb/ksu")
+        || path_str.starts_with("/data/adb/ap")
+        || (root_source.is_some() && source_str == root_source)
 }
 
-impl MountNamespaceManager {
-    /// Creates a new, empty `MountNamespaceManager`.
+/// If the active root implementation is KernelSU, returns the `mount_source`
+/// of the `/data/adb/modules` mount, when it's backed by a loop device.
+///
+/// KernelSU bind-mounts copies of that same loop device elsewhere (outside
+/// `/data/adb/modules`), and those copies are just as much a root trace as
+/// the primary mount, but aren't caught by [`is_root_related`]'s path-based
+/// checks since they don't live under a recognizable path -- callers need to
+/// separately match any other mount whose `mount_source` equals this one.
+pub(crate) fn ksu_module_loop_source(mount_infos: &[MountInfo]) -> Option<String> {
+    if !matches!(root_impl::get(), root_impl::RootImpl::KernelSU) {
+        return None;
+    }
+    mount_infos
+        .iter()
+        .find(|info| info.mount_point.as_path().to_str() == Some("/data/adb/modules"))
+        .and_then(|info| info.mount_source.clone())
+        .filter(|source| source.starts_with("/dev/block/loop"))
+}
+
+/// Manages the lifecycle and caching of namespace file descriptors across
+/// more than just the mount axis.
+///
+/// This manager is responsible for creating and holding onto file
+/// descriptors that represent specific namespaces, preventing them from
+/// being destroyed, so the daemon can cheaply switch into them again later
+/// without re-forking a holder process.
+pub struct NamespaceManager {
+    cache: Mutex<HashMap<(NamespaceKind, MountNamespace), Arc<OnceLock<OwnedFd>>>>,
+}
+
+/// The mount-namespace-only manager this daemon used before namespace
+/// caching was generalized. Kept as an alias so existing call sites that
+/// only ever dealt with mount namespaces keep working unchanged.
+pub type MountNamespaceManager = NamespaceManager;
+
+impl NamespaceManager {
+    /// Creates a new, empty `NamespaceManager`.
     pub fn new() -> Self {
         Self {
-            clean_mnt_ns_fd: OnceLock::new(),
-            root_mnt_ns_fd: OnceLock::new(),
+            cache: Mutex::new(HashMap::new()),
         }
     }
 
-    fn get_namespace_storage(&self, namespace_type: MountNamespace) -> &OnceLock<OwnedFd> {
-        match namespace_type {
-            MountNamespace::Clean => &self.clean_mnt_ns_fd,
-            MountNamespace::Root => &self.root_mnt_ns_fd,
-        }
+    /// Gets the cached file descriptor for a given namespace kind/variant,
+    /// if it exists. Kept under the old name for mount-only call sites.
+    pub fn get_namespace_fd(&self, namespace_type: MountNamespace) -> Option<RawFd> {
+        self.get_cached_fd(NamespaceKind::Mount, namespace_type)
     }
 
-    /// Gets the cached file descriptor for a given namespace type, if it exists.
-    pub fn get_namespace_fd(&self, namespace_type: MountNamespace) -> Option<RawFd> {
-        self.get_namespace_storage(namespace_type)
-            .get()
+    /// Gets the cached file descriptor for a given `(kind, variant)` pair,
+    /// if it exists.
+    pub fn get_cached_fd(&self, kind: NamespaceKind, variant: MountNamespace) -> Option<RawFd> {
+        self.cache
+            .lock()
+            .unwrap()
+            .get(&(kind, variant))
+            .and_then(|slot| slot.get())
             .map(|fd| fd.as_raw_fd())
     }
 
-    /// Caches a handle to a specific mount namespace (`Clean` or `Root`).
+    /// Caches a handle to a mount namespace (`Clean` or `Root`). Kept under
+    /// the old name for mount-only call sites.
+    pub fn save_mount_namespace(&self, pid: i32, namespace_type: MountNamespace) -> Result<RawFd> {
+        self.save_namespace(pid, NamespaceKind::Mount, namespace_type)
+    }
+
+    /// Caches a handle to a specific namespace of a target process.
     ///
     /// # Arguments
-    /// * `pid` - The PID of a process currently in the target mount namespace.
-    /// * `namespace_type` - The type of namespace to save.
-    pub fn save_mount_namespace(&self, pid: i32, namespace_type: MountNamespace) -> Result<RawFd> {
-        let ns_fd_cell = self.get_namespace_storage(namespace_type);
-        if let Some(fd) = ns_fd_cell.get() {
+    /// * `pid` - The PID of a process currently in the target namespace.
+    /// * `kind` - Which namespace axis to cache (mount, UTS, net, cgroup).
+    ///   `NamespaceKind::Pid` is rejected with an error.
+    /// * `namespace_type` - Whether to cache the process's namespace as-is
+    ///   (`Root`) or a `Clean` copy with root-related mounts removed.
+    pub fn save_namespace(
+        &self,
+        pid: i32,
+        kind: NamespaceKind,
+        namespace_type: MountNamespace,
+    ) -> Result<RawFd> {
+        // Claim (or join) the slot for this `(kind, namespace_type)` under
+        // the lock, then release it before doing the expensive fork/setns
+        // work below. The `OnceLock` inside the slot -- not the outer
+        // `Mutex` -- is what actually arbitrates the race: if two callers
+        // both see an empty slot and both build a namespace fd, only one
+        // `OnceLock::set()` call can win, and the loser discards its own
+        // (redundant) fd instead of silently clobbering the winner's.
+        let slot = self
+            .cache
+            .lock()
+            .unwrap()
+            .entry((kind, namespace_type))
+            .or_insert_with(|| Arc::new(OnceLock::new()))
+            .clone();
+
+        if let Some(fd) = slot.get() {
             return Ok(fd.as_raw_fd());
         }
 
+        if kind == NamespaceKind::Pid {
+            // setns(CLONE_NEWPID) doesn't move the calling thread into the
+            // target's PID namespace either -- only its future children land
+            // there (see setns(2)'s PID-namespace caveat), same restriction
+            // as unshare(CLONE_NEWPID). That breaks both variants here, not
+            // just `Clean`: for `Root` the child below calls
+            // switch_namespace(pid, Pid) (a setns) and then just signals and
+            // sleeps, so the parent's subsequent
+            // /proc/{child_pid}/ns/pid open still reads the *daemon's own*
+            // PID namespace, silently caching the wrong fd. Refuse PID
+            // namespace caching entirely until a child-of-the-joined-child
+            // fork is implemented to actually capture the target namespace.
+            bail!("PID namespace caching is not supported yet");
+        }
+
         // Create a pipe for synchronization between parent and child.
         let (pipe_reader, pipe_writer) = rustix::pipe::pipe()?;
 
@@ -102,21 +358,34 @@ impl MountNamespaceManager {
             0 => {
                 // --- Child Process ---
                 drop(pipe_reader); // Close the side of the pipe we don't use.
-                switch_mount_namespace(pid).unwrap();
+                switch_namespace(pid, kind).unwrap();
 
                 if namespace_type == MountNamespace::Clean {
-                    // Create a new, private mount namespace for ourselves.
+                    // Create a new, private namespace of this kind for
+                    // ourselves.
                     unsafe {
-                        rustix_thread::unshare_unsafe(rustix_thread::UnshareFlags::NEWNS).unwrap();
+                        rustix_thread::unshare_unsafe(kind.unshare_flags()).unwrap();
+                    }
+                    if kind == NamespaceKind::Mount {
+                        // Unmount all root and module mounts. No analogous
+                        // cleanup is defined yet for the other namespace
+                        // kinds; `Clean` there currently just means "freshly
+                        // unshared".
+                        Self::clean_mount_namespace().unwrap();
                     }
-                    // Unmount all root and module mounts.
-                    Self::clean_mount_namespace().unwrap();
                 }
 
                 // Signal to the parent that setup is complete.
                 let sig: [u8; 1] = [0];
                 rustix::io::write(pipe_writer, &sig).unwrap();
 
+                // From here on we only ever sleep until the parent kills us,
+                // so lock ourselves down to that narrow set of syscalls. We
+                // still share the daemon's address space and capabilities at
+                // this point, so this meaningfully shrinks our attack
+                // surface for the rest of our (short) life.
+                namespace_holder_seccomp::apply().unwrap();
+
                 // Wait indefinitely. The parent will kill us after it has the FD.
                 loop {
                     std::thread::sleep(std::time::Duration::from_secs(60));
@@ -126,27 +395,52 @@ impl MountNamespaceManager {
                 // --- Parent Process ---
                 drop(pipe_writer);
 
+                // Open a pidfd on the child as early as possible, before
+                // waiting for its signal, so the pid-reuse window below is
+                // pinned to this exact child regardless of how long setup
+                // takes.
+                let child_pidfd = pidfd_open(child_pid)?;
+
                 // Wait for the signal from the child.
                 let mut buf: [u8; 1] = [0];
                 rustix::io::read(pipe_reader, &mut buf)?;
-                trace!("Child {} finished setting up mount namespace.", child_pid);
+                trace!("Child {} finished setting up {:?} namespace.", child_pid, kind);
 
-                let ns_path = format!("/proc/{}/ns/mnt", child_pid);
+                let ns_path = format!("/proc/{}/ns/{}", child_pid, kind.proc_name());
                 let ns_file = fs::File::open(&ns_path)?;
 
                 // We have the FD, we can now terminate the child process.
+                // Prefer pidfd_send_signal so we can't accidentally kill a
+                // pid that was reused after the child exited on its own.
+                match &child_pidfd {
+                    Some(pidfd) => pidfd_send_signal(pidfd.as_raw_fd(), libc::SIGKILL)?,
+                    None => unsafe {
+                        libc::kill(child_pid, libc::SIGKILL);
+                    },
+                }
                 unsafe {
-                    libc::kill(child_pid, libc::SIGKILL);
                     libc::waitpid(child_pid, std::ptr::null_mut(), 0);
                 }
 
-                let raw_fd = ns_file.as_raw_fd();
-                ns_fd_cell
-                    .set(ns_file.into())
-                    .map_err(|_| anyhow::anyhow!("Failed to set OnceLock for namespace FD"))?;
-
-                trace!("{:?} namespace cached as FD {}", namespace_type, raw_fd);
-                Ok(raw_fd)
+                match slot.set(ns_file.into()) {
+                    Ok(()) => {
+                        let raw_fd = slot.get().unwrap().as_raw_fd();
+                        trace!("{:?} {:?} namespace cached as FD {}", kind, namespace_type, raw_fd);
+                        Ok(raw_fd)
+                    }
+                    Err(_our_fd) => {
+                        // Another caller already populated this slot while we
+                        // were forking and setns-ing; `_our_fd` drops here,
+                        // closing our now-redundant fd, and we hand back the
+                        // winner's instead.
+                        trace!(
+                            "Lost the race to cache {:?} {:?} namespace; reusing the winner's FD",
+                            kind,
+                            namespace_type
+                        );
+                        Ok(slot.get().unwrap().as_raw_fd())
+                    }
+                }
             }
             _ => bail!(Error::last_os_error()),
         }
@@ -164,24 +458,10 @@ impl MountNamespaceManager {
             _ => None,
         };
 
-        let ksu_module_source: Option<String> =
-            if matches!(root_impl::get(), root_impl::RootImpl::KernelSU) {
-                mount_infos
-                    .iter()
-                    .find(|info| info.mount_point.as_path().to_str() == Some("/data/adb/modules"))
-                    .and_then(|info| info.mount_source.clone())
-                    .filter(|source| source.starts_with("/dev/block/loop"))
-            } else {
-                None
-            };
+        let ksu_module_source = ksu_module_loop_source(&mount_infos);
 
         for info in mount_infos {
-            let path_str = info.mount_point.to_str().unwrap_or("");
-            let mount_source_str = info.mount_source.as_deref();
-
-            let should_unmount = info.root.starts_with("/adb/modules")
-                || path_str.starts_with("/data/adb/modules")
-                || (root_source.is_some() && mount_source_str == root_source)
+            let should_unmount = is_root_related(&info, root_source)
                 || (ksu_module_source.is_some() && info.mount_source == ksu_module_source);
 
             if should_unmount {
@@ -207,8 +487,191 @@ impl MountNamespaceManager {
     }
 }
 
-impl Default for MountNamespaceManager {
+impl Default for NamespaceManager {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// Installs the seccomp-BPF filter worn by the namespace-holder child once
+/// it has signalled readiness and has nothing left to do but sleep.
+mod namespace_holder_seccomp {
+    use crate::lp_select;
+    use anyhow::{Result, bail};
+    use std::io::Error;
+
+    // --- Classic BPF opcodes (linux/filter.h) ---
+    const BPF_LD: u16 = 0x00;
+    const BPF_W: u16 = 0x00;
+    const BPF_ABS: u16 = 0x20;
+    const BPF_JMP: u16 = 0x05;
+    const BPF_JEQ: u16 = 0x10;
+    const BPF_K: u16 = 0x00;
+    const BPF_RET: u16 = 0x06;
+
+    // --- seccomp return actions (linux/seccomp.h) ---
+    const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+    /// Mirrors the kernel's `struct seccomp_data`; we only need the `nr`
+    /// field (offset 0), but the full layout documents where it comes from.
+    #[repr(C)]
+    struct SeccompData {
+        nr: u32,
+        arch: u32,
+        instruction_pointer: u64,
+        args: [u64; 6],
+    }
+
+    fn stmt(code: u16, k: u32) -> libc::sock_filter {
+        libc::sock_filter {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+        libc::sock_filter { code, jt, jf, k }
+    }
+
+    /// The syscalls the namespace-holder child still needs once it has
+    /// signalled readiness: sleeping (`std::thread::sleep` issues
+    /// `nanosleep`; `clock_nanosleep` is allowed as well), being interrupted
+    /// and restarting the sleep, and exiting when killed. Both sleep calls
+    /// exist as syscalls on 32- and 64-bit targets, so both lists allow them.
+    fn allowed_syscalls() -> Vec<i64> {
+        lp_select!(
+            vec![
+                libc::SYS_nanosleep,
+                libc::SYS_clock_nanosleep,
+                libc::SYS_restart_syscall,
+                libc::SYS_exit,
+                libc::SYS_exit_group,
+                libc::SYS_rt_sigreturn,
+            ],
+            vec![
+                libc::SYS_nanosleep,
+                libc::SYS_clock_nanosleep,
+                libc::SYS_restart_syscall,
+                libc::SYS_exit,
+                libc::SYS_exit_group,
+                libc::SYS_rt_sigreturn,
+            ]
+        )
+    }
+
+    fn build_program() -> Vec<libc::sock_filter> {
+        let syscalls = allowed_syscalls();
+        // Each allowed syscall gets one comparison; `jt` jumps forward past
+        // the remaining comparisons straight to the ALLOW instruction.
+        let mut program = Vec::with_capacity(syscalls.len() + 2);
+        program.push(stmt(
+            BPF_LD | BPF_W | BPF_ABS,
+            std::mem::offset_of!(SeccompData, nr) as u32,
+        ));
+
+        for (i, syscall) in syscalls.iter().enumerate() {
+            // Distance from this instruction to the ALLOW instruction, which
+            // sits right after the last comparison. `jt` is relative to the
+            // *next* instruction, so the last comparison (i == len - 1)
+            // needs jt == 0, not 1.
+            let jt = (syscalls.len() - i - 1) as u8;
+            // The no-match fallthrough (`jf`) normally just steps to the
+            // next comparison. For the *last* comparison that fallthrough
+            // would otherwise land on RET ALLOW instead of skipping past it
+            // to RET KILL_PROCESS, so it alone needs an extra hop.
+            let jf = if i == syscalls.len() - 1 { 1 } else { 0 };
+            program.push(jump(BPF_JMP | BPF_JEQ | BPF_K, *syscall as u32, jt, jf));
+        }
+
+        program.push(stmt(BPF_RET, SECCOMP_RET_ALLOW));
+        program.push(stmt(BPF_RET, SECCOMP_RET_KILL_PROCESS));
+        program
+    }
+
+    /// Applies the allow-list filter to the calling thread/process. Once
+    /// applied, any syscall outside the allow-list kills the process
+    /// immediately rather than returning an error.
+    pub fn apply() -> Result<()> {
+        let mut program = build_program();
+        let fprog = libc::sock_fprog {
+            len: program.len() as u16,
+            filter: program.as_mut_ptr(),
+        };
+
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                bail!(Error::last_os_error());
+            }
+            if libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER,
+                &fprog as *const libc::sock_fprog,
+            ) != 0
+            {
+                bail!(Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// A minimal classic-BPF interpreter covering just the instructions
+        /// `build_program` emits, so we can check the generated filter's
+        /// jump offsets against every syscall ourselves rather than trusting
+        /// them by inspection.
+        fn run(program: &[libc::sock_filter], nr: i64) -> u32 {
+            let data = SeccompData {
+                nr: nr as u32,
+                arch: 0,
+                instruction_pointer: 0,
+                args: [0; 6],
+            };
+            let mut pc = 0usize;
+            loop {
+                let insn = &program[pc];
+                match insn.code {
+                    c if c == BPF_LD | BPF_W | BPF_ABS => {
+                        assert_eq!(insn.k, std::mem::offset_of!(SeccompData, nr) as u32);
+                        pc += 1;
+                    }
+                    c if c == BPF_JMP | BPF_JEQ | BPF_K => {
+                        pc += 1 + if data.nr == insn.k {
+                            insn.jt as usize
+                        } else {
+                            insn.jf as usize
+                        };
+                    }
+                    BPF_RET => return insn.k,
+                    other => panic!("interpreter doesn't model opcode {}", other),
+                }
+            }
+        }
+
+        #[test]
+        fn allowed_syscalls_reach_allow() {
+            let program = build_program();
+            for syscall in allowed_syscalls() {
+                assert_eq!(
+                    run(&program, syscall),
+                    SECCOMP_RET_ALLOW,
+                    "syscall {} should be allowed",
+                    syscall
+                );
+            }
+        }
+
+        #[test]
+        fn other_syscalls_reach_kill() {
+            let program = build_program();
+            let disallowed = libc::SYS_openat;
+            assert!(!allowed_syscalls().contains(&disallowed));
+            assert_eq!(run(&program, disallowed), SECCOMP_RET_KILL_PROCESS);
+        }
+    }
+}
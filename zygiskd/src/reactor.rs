@@ -0,0 +1,305 @@
+// src/reactor.rs
+
+//! An epoll-based reactor for the daemon's Unix socket.
+//!
+//! Services many concurrent Zygote/companion connections on a single
+//! thread with O(1) wakeups from `epoll_wait`, instead of checking each
+//! connection's liveness independently (see the old `is_socket_alive`
+//! poll-per-fd approach in `utils.rs`). Connections are dropped as soon as
+//! `EPOLLRDHUP`/`EPOLLHUP` fires, rather than being discovered dead the
+//! next time something happens to poll them.
+//!
+//! Staged: see the "In-Progress Wiring" note in `main.rs` -- nothing drives
+//! an [`EventLoop`]/[`WorkerPool`] yet.
+
+use anyhow::Result;
+use log::{debug, trace, warn};
+use std::collections::HashMap;
+use std::io::Error;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+
+use crate::constants::DaemonSocketAction;
+use crate::utils::UnixStreamExt;
+
+/// A fixed-size pool of worker threads that drain a shared job queue.
+///
+/// Used to hand a whole connection's conversation off the epoll thread
+/// once it's been accepted, so a slow companion `fork`+`exec` for one app
+/// can't delay module-FD delivery to every other connection the reactor is
+/// servicing (mirrors Magisk's `register_poll`/`exec_task` split between
+/// accepting and handling).
+pub struct WorkerPool {
+    sender: mpsc::Sender<Box<dyn FnOnce() + Send>>,
+}
+
+impl WorkerPool {
+    /// Spawns `size` worker threads, each blocking on the shared job queue.
+    pub fn new(size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Box<dyn FnOnce() + Send>>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..size {
+            let receiver = Arc::clone(&receiver);
+            std::thread::spawn(move || {
+                while let Ok(job) = receiver.lock().unwrap().recv() {
+                    job();
+                }
+            });
+        }
+        Self { sender }
+    }
+
+    /// Queues `job` to run on the next free worker thread.
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, job: F) {
+        // The receiver side only goes away if every worker thread panicked;
+        // there's nothing useful to do about a dropped job in that case.
+        let _ = self.sender.send(Box::new(job));
+    }
+}
+
+/// The event mask registered for every connection: readable data, and the
+/// three ways a peer can go away without ever becoming readable.
+const CONNECTION_EVENTS: u32 =
+    (libc::EPOLLIN | libc::EPOLLRDHUP | libc::EPOLLERR | libc::EPOLLHUP) as u32;
+
+/// Implemented by whatever wants to react to requests coming in on
+/// accepted connections.
+///
+/// Takes `&self` rather than `&mut self` because `run_with_pool` may call
+/// it concurrently from several worker threads; implementations that need
+/// mutable state should hold it behind their own `Mutex`/atomics.
+///
+/// Returning `Ok(false)` (or an `Err`) tells the reactor to close and
+/// deregister the connection; returning `Ok(true)` keeps it open for
+/// further requests.
+pub trait DaemonSocketHandler {
+    fn handle(&self, action: DaemonSocketAction, stream: &mut UnixStream) -> Result<bool>;
+}
+
+/// A connection slot tracked by the reactor.
+enum Connection {
+    /// The listening socket; readable means a new connection is pending.
+    Listener(UnixListener),
+    /// An accepted client connection (Zygote, a companion, ...).
+    Client(UnixStream),
+}
+
+/// An epoll-backed reactor that multiplexes the daemon's listening socket
+/// and all of its accepted connections on a single thread.
+pub struct EventLoop {
+    epoll_fd: OwnedFd,
+    connections: HashMap<RawFd, Connection>,
+}
+
+impl EventLoop {
+    /// Creates a reactor and registers `listener` for incoming connections.
+    pub fn new(listener: UnixListener) -> Result<Self> {
+        let epoll_fd = unsafe {
+            let fd = libc::epoll_create1(0);
+            if fd < 0 {
+                return Err(Error::last_os_error().into());
+            }
+            OwnedFd::from_raw_fd(fd)
+        };
+
+        let mut event_loop = Self {
+            epoll_fd,
+            connections: HashMap::new(),
+        };
+        let listener_fd = listener.as_raw_fd();
+        event_loop.register(listener_fd, libc::EPOLLIN as u32)?;
+        event_loop
+            .connections
+            .insert(listener_fd, Connection::Listener(listener));
+        Ok(event_loop)
+    }
+
+    fn register(&self, fd: RawFd, events: u32) -> Result<()> {
+        let mut event = libc::epoll_event {
+            events,
+            u64: fd as u64,
+        };
+        let ret =
+            unsafe { libc::epoll_ctl(self.epoll_fd.as_raw_fd(), libc::EPOLL_CTL_ADD, fd, &mut event) };
+        if ret != 0 {
+            return Err(Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    fn deregister(&self, fd: RawFd) {
+        // The kernel drops the registration automatically when the fd is
+        // closed, but we may still be holding other copies of it around
+        // (there aren't any today, this is just defensive).
+        unsafe {
+            libc::epoll_ctl(
+                self.epoll_fd.as_raw_fd(),
+                libc::EPOLL_CTL_DEL,
+                fd,
+                std::ptr::null_mut(),
+            );
+        }
+    }
+
+    /// Runs the reactor loop, dispatching readable connections to
+    /// `handler`. Never returns unless `epoll_wait` itself fails.
+    pub fn run<H: DaemonSocketHandler>(&mut self, handler: &H) -> Result<()> {
+        let mut events = vec![libc::epoll_event { events: 0, u64: 0 }; 64];
+        loop {
+            let n = unsafe {
+                libc::epoll_wait(
+                    self.epoll_fd.as_raw_fd(),
+                    events.as_mut_ptr(),
+                    events.len() as i32,
+                    -1,
+                )
+            };
+            if n < 0 {
+                let err = Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err.into());
+            }
+
+            for event in &events[..n as usize] {
+                let fd = event.u64 as RawFd;
+                self.dispatch(fd, event.events, handler);
+            }
+        }
+    }
+
+    /// Runs the reactor loop like [`Self::run`], but hands each
+    /// connection's entire conversation off to `pool` the moment it
+    /// becomes readable, instead of calling `handler` inline on the epoll
+    /// thread. The connection is deregistered from epoll for the handoff;
+    /// the worker owns it exclusively (doing its own blocking reads) from
+    /// then on, so this reactor never sees it again.
+    pub fn run_with_pool<H>(&mut self, handler: Arc<H>, pool: &WorkerPool) -> Result<()>
+    where
+        H: DaemonSocketHandler + Send + Sync + 'static,
+    {
+        let mut events = vec![libc::epoll_event { events: 0, u64: 0 }; 64];
+        loop {
+            let n = unsafe {
+                libc::epoll_wait(
+                    self.epoll_fd.as_raw_fd(),
+                    events.as_mut_ptr(),
+                    events.len() as i32,
+                    -1,
+                )
+            };
+            if n < 0 {
+                let err = Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err.into());
+            }
+
+            for event in &events[..n as usize] {
+                let fd = event.u64 as RawFd;
+                let is_listener = matches!(self.connections.get(&fd), Some(Connection::Listener(_)));
+                if is_listener {
+                    self.accept_one(fd);
+                    continue;
+                }
+
+                self.deregister(fd);
+                if let Some(Connection::Client(stream)) = self.connections.remove(&fd) {
+                    let handler = Arc::clone(&handler);
+                    pool.execute(move || Self::drive_connection(stream, handler.as_ref()));
+                }
+            }
+        }
+    }
+
+    /// Services one connection's full request/response conversation on
+    /// whichever worker thread picked it up, until the peer goes away or
+    /// sends something the handler rejects.
+    fn drive_connection<H: DaemonSocketHandler>(mut stream: UnixStream, handler: &H) {
+        loop {
+            let action = match stream.read_u8().map(DaemonSocketAction::try_from) {
+                Ok(Ok(action)) => action,
+                Ok(Err(_)) => {
+                    warn!("Worker: connection sent an unknown action byte.");
+                    return;
+                }
+                Err(_) => return,
+            };
+            match handler.handle(action, &mut stream) {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => {
+                    warn!("Worker: handler failed: {:?}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn dispatch<H: DaemonSocketHandler>(&mut self, fd: RawFd, events: u32, handler: &H) {
+        // Check which variant this is without holding a borrow of
+        // `self.connections` across `self.accept_one`, which needs its own
+        // `&mut self` (mirrors `run_with_pool`'s same two-step check).
+        let is_listener = matches!(self.connections.get(&fd), Some(Connection::Listener(_)));
+        if is_listener {
+            self.accept_one(fd);
+            return;
+        }
+
+        let hung_up = events & (libc::EPOLLRDHUP | libc::EPOLLERR | libc::EPOLLHUP) as u32 != 0;
+        if hung_up {
+            trace!("Connection {} hung up, dropping.", fd);
+            self.close(fd);
+            return;
+        }
+
+        let Some(Connection::Client(stream)) = self.connections.get_mut(&fd) else {
+            return;
+        };
+        match stream.read_u8().map(DaemonSocketAction::try_from) {
+            Ok(Ok(action)) => match handler.handle(action, stream) {
+                Ok(true) => {}
+                Ok(false) => self.close(fd),
+                Err(e) => {
+                    warn!("Handler failed for connection {}: {:?}", fd, e);
+                    self.close(fd);
+                }
+            },
+            Ok(Err(_)) => {
+                warn!("Connection {} sent an unknown action byte.", fd);
+                self.close(fd);
+            }
+            Err(_) => self.close(fd),
+        }
+    }
+
+    /// Accepts one pending connection. The listener is level-triggered, so
+    /// if more than one connection arrived between wakeups, `epoll_wait`
+    /// will simply report it readable again on the next iteration.
+    fn accept_one(&mut self, listener_fd: RawFd) {
+        let Some(Connection::Listener(listener)) = self.connections.get(&listener_fd) else {
+            return;
+        };
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let fd = stream.as_raw_fd();
+                if let Err(e) = self.register(fd, CONNECTION_EVENTS) {
+                    warn!("Failed to register new connection: {:?}", e);
+                    return;
+                }
+                debug!("Accepted new connection on fd {}.", fd);
+                self.connections.insert(fd, Connection::Client(stream));
+            }
+            Err(e) => warn!("Failed to accept connection: {:?}", e),
+        }
+    }
+
+    fn close(&mut self, fd: RawFd) {
+        self.deregister(fd);
+        self.connections.remove(&fd);
+    }
+}
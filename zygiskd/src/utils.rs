@@ -7,15 +7,17 @@
 //! - Low-level Unix socket and pipe I/O.
 //! - A trait (`UnixStreamExt`) for simplified socket communication.
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use rustix::net::{
     AddressFamily, SendFlags, SocketAddrUnix, SocketType, bind, connect, listen, sendto, socket,
 };
 use rustix::thread as rustix_thread;
-use std::ffi::{CString, c_char};
-use std::os::fd::AsRawFd;
+use std::ffi::{CString, c_char, c_void};
+use std::io::Error;
+use std::os::fd::{AsRawFd, RawFd};
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::process::Command;
+use std::time::{Duration, Instant};
 use std::{
     fs,
     io::{Read, Write},
@@ -97,6 +99,73 @@ pub fn get_property(name: &str) -> Result<String> {
     }
 }
 
+/// Converts the time remaining until `deadline` into a `libc::timespec`,
+/// bailing out if the deadline has already passed.
+fn remaining_timespec(deadline: Instant) -> Result<libc::timespec> {
+    let remaining = deadline
+        .checked_duration_since(Instant::now())
+        .ok_or_else(|| anyhow::anyhow!("timed out"))?;
+    Ok(libc::timespec {
+        tv_sec: remaining.as_secs() as libc::time_t,
+        tv_nsec: remaining.subsec_nanos() as libc::c_long,
+    })
+}
+
+/// Blocks until an Android system property named `name` exists and equals
+/// `expected` (or, if `expected` is `None`, until it simply exists), waiting
+/// up to `timeout` in total.
+///
+/// Unlike [`get_property`], which does a one-shot read and returns an empty
+/// string for a property that hasn't been set yet, this also handles the
+/// case where `name` does not exist when the wait begins: it first waits on
+/// the global property area for `__system_property_find` to start
+/// succeeding, then waits on the property itself until its value matches.
+/// This is what lets callers react to properties created after the daemon
+/// starts, such as boot-stage markers set by a service that starts late.
+pub fn wait_for_property(name: &str, expected: Option<&str>, timeout: Duration) -> Result<String> {
+    let deadline = Instant::now() + timeout;
+    let cname = CString::new(name)?;
+
+    let mut pi = unsafe { __system_property_find(cname.as_ptr()) };
+    while pi.is_null() {
+        let area_serial = unsafe { __system_property_area_serial() };
+        let mut new_serial = 0u32;
+        let ts = remaining_timespec(deadline)?;
+        if !unsafe { __system_property_wait(std::ptr::null(), area_serial, &mut new_serial, &ts) } {
+            bail!("timed out waiting for property '{}' to be created", name);
+        }
+        pi = unsafe { __system_property_find(cname.as_ptr()) };
+    }
+
+    // Seed `serial` from `pi`'s actual current serial rather than `0`: a
+    // property's serial is essentially never `0` once it exists, so
+    // starting from `0` would make the first `__system_property_wait` call
+    // below return immediately (its serial has already "changed" from 0)
+    // without blocking, even though the value may still mismatch.
+    let mut serial = unsafe { __system_property_serial(pi) };
+    loop {
+        let value = get_property(name)?;
+        let matches = match expected {
+            Some(exp) => exp == value,
+            None => true,
+        };
+        if matches {
+            return Ok(value);
+        }
+
+        let mut new_serial = 0u32;
+        let ts = remaining_timespec(deadline)?;
+        if !unsafe { __system_property_wait(pi, serial, &mut new_serial, &ts) } {
+            bail!(
+                "timed out waiting for property '{}' to become {:?}",
+                name,
+                expected
+            );
+        }
+        serial = new_serial;
+    }
+}
+
 // --- Unix Socket and IPC Extensions ---
 
 /// An extension trait for `UnixStream` to simplify reading and writing common data types.
@@ -182,6 +251,10 @@ pub fn unix_datagram_sendto(path: &str, buf: &[u8]) -> Result<()> {
 }
 
 /// Checks if a Unix socket is still alive and connected using `poll`.
+///
+/// This checks a single fd at a time; an accept loop servicing many
+/// connections should prefer `reactor::EventLoop`, which multiplexes all
+/// of them with `epoll` instead of polling each one in turn.
 pub fn is_socket_alive(stream: &UnixStream) -> bool {
     let pfd = libc::pollfd {
         fd: stream.as_raw_fd(),
@@ -198,8 +271,145 @@ pub fn is_socket_alive(stream: &UnixStream) -> bool {
     pfds[0].revents & !libc::POLLIN == 0
 }
 
+// --- File Descriptor Passing (SCM_RIGHTS) ---
+
+/// Sends a single open file descriptor to the peer of `stream` via
+/// `SCM_RIGHTS` ancillary data.
+///
+/// Written for the module-FD response and the companion handoff, and for
+/// handing modules/companions a logd fd so their log writes flow through
+/// the same transport as the daemon's own, even after the daemon has
+/// otherwise dropped out of the conversation.
+///
+/// Staged: see the "In-Progress Wiring" note in `main.rs` -- nothing calls
+/// this yet.
+pub fn send_fd(stream: &UnixStream, fd: RawFd) -> Result<()> {
+    // A single marker byte so the peer's `recvmsg` has a normal data
+    // payload to go with the ancillary data; some kernels drop `SCM_RIGHTS`
+    // sent with a zero-length payload.
+    let data = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut c_void,
+        iov_len: data.len(),
+    };
+
+    let cmsg_len = unsafe { libc::CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) } as usize;
+    let mut cmsg_buf = vec![0u8; cmsg_len];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut c_void;
+    msg.msg_controllen = cmsg_len;
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<RawFd>() as u32) as _;
+        std::ptr::write(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+    }
+
+    let ret = unsafe { libc::sendmsg(stream.as_raw_fd(), &msg, 0) };
+    if ret < 0 {
+        bail!(Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Receives a single file descriptor sent by a peer via [`send_fd`].
+///
+/// Requests `MSG_CMSG_CLOEXEC` so the received fd comes back close-on-exec:
+/// the daemon later `fork()`s+`exec()`s companion processes, and without
+/// this flag the fd would be silently inherited across every such exec
+/// until something remembered to close it explicitly.
+pub fn recv_fd(stream: &UnixStream) -> Result<RawFd> {
+    let mut data = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut c_void,
+        iov_len: data.len(),
+    };
+
+    let cmsg_len = unsafe { libc::CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) } as usize;
+    let mut cmsg_buf = vec![0u8; cmsg_len];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut c_void;
+    msg.msg_controllen = cmsg_len;
+
+    let ret = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) };
+    if ret < 0 {
+        bail!(Error::last_os_error());
+    }
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        if cmsg.is_null() || (*cmsg).cmsg_type != libc::SCM_RIGHTS {
+            bail!("peer did not send a file descriptor");
+        }
+        Ok(std::ptr::read(libc::CMSG_DATA(cmsg) as *const RawFd))
+    }
+}
+
+// --- Log Record Framing ---
+
+/// A single log record as written by a module or companion process through
+/// its inherited logd fd (see [`send_fd`]), framed so the daemon can tee
+/// the same bytes into its own output.
+///
+/// Staged: see the "In-Progress Wiring" note in `main.rs` -- nothing sends
+/// or receives one of these yet.
+pub struct LogRecord {
+    pub priority: u8,
+    pub tag: String,
+    pub message: String,
+}
+
+impl LogRecord {
+    /// Writes this record to `stream` using the same framing every other
+    /// request on the daemon's sockets uses, via [`UnixStreamExt`].
+    pub fn write_to(&self, stream: &mut UnixStream) -> Result<()> {
+        stream.write_u8(self.priority)?;
+        stream.write_string(&self.tag)?;
+        stream.write_string(&self.message)?;
+        Ok(())
+    }
+
+    /// Reads a record written by [`Self::write_to`].
+    pub fn read_from(stream: &mut UnixStream) -> Result<Self> {
+        let priority = stream.read_u8()?;
+        let tag = stream.read_string()?;
+        let message = stream.read_string()?;
+        Ok(Self {
+            priority,
+            tag,
+            message,
+        })
+    }
+}
+
 // --- FFI for Android System APIs ---
 unsafe extern "C" {
     fn __system_property_get(name: *const c_char, value: *mut c_char) -> u32;
-    // Other __system_property functions could be declared here if needed.
+    /// Looks up a property by name, returning an opaque handle or null if
+    /// the property does not exist (yet).
+    fn __system_property_find(name: *const c_char) -> *const c_void;
+    /// Blocks until `pi`'s serial changes from `old_serial`, or until
+    /// `timeout` elapses. If `pi` is null, waits on the global property
+    /// area's serial instead, which changes whenever any property is
+    /// created. Returns `false` on timeout.
+    fn __system_property_wait(
+        pi: *const c_void,
+        old_serial: u32,
+        new_serial_out: *mut u32,
+        timeout: *const libc::timespec,
+    ) -> bool;
+    /// Returns the current serial of the global property area.
+    fn __system_property_area_serial() -> u32;
+    /// Returns `pi`'s current serial, i.e. the value `__system_property_wait`
+    /// needs as `old_serial` to actually block until the *next* change
+    /// rather than returning immediately because `old_serial` is stale.
+    fn __system_property_serial(pi: *const c_void) -> u32;
 }